@@ -5,9 +5,10 @@ pub mod walker;
 pub use walker::{vault_contents, WalkOptions};
 
 use pulldown_cmark::{CowStr, Event, Options, Parser as MdParser, Tag, TagEnd};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::references::{ObsidianNoteReference, RefParser, RefParserState, RefType};
+use serde_yaml::Value;
 use snafu::Snafu;
 use unicode_normalization::UnicodeNormalization;
 
@@ -18,32 +19,210 @@ pub enum ExportError {
     WalkDirError { path: PathBuf, source: ignore::Error },
 }
 
+/// The outcome of resolving a wiki-link reference against the vault contents.
+#[derive(Debug)]
+#[allow(clippy::exhaustive_enums)]
+pub enum LinkResolution<'a> {
+    /// No file in the vault matched the reference.
+    NotFound,
+    /// A single file is the unambiguous match: either the only file that matched, or the one
+    /// strictly closer to the linking note than every other match.
+    Found(&'a PathBuf),
+    /// Two or more files are equally close to the linking note. All tied
+    /// candidates are returned, ordered deterministically (shortest path
+    /// first, then lexicographically), so the caller can warn or error.
+    Ambiguous(Vec<&'a PathBuf>),
+}
+
 /// Get the full path for the given filename when it's contained in `vault_contents`, taking into
 /// account:
 ///
 /// 1. Standard Obsidian note references not including a .md extension.
 /// 2. Case-insensitive matching
 /// 3. Unicode normalization rules using normalization form C (<https://www.w3.org/TR/charmod-norm/#unicodeNormalization>)
+///
+/// When several files share a basename (or matching partial path), candidates are ranked by
+/// directory proximity to `source`, mirroring how Obsidian resolves a bare `[[name]]` to the
+/// closest note rather than an arbitrary one. Candidates tied on proximity are reported via
+/// [`LinkResolution::Ambiguous`] instead of silently picking one.
 pub fn lookup_filename_in_vault<'a>(
     filename: &str,
     vault_contents: &'a [PathBuf],
-) -> Option<&'a PathBuf> {
+    source: &Path,
+) -> LinkResolution<'a> {
     let filename = PathBuf::from(filename);
     let filename_normalized = filename.to_string_lossy().nfc().collect::<String>();
 
-    vault_contents.iter().find(|path| {
-        let path_normalized_str = path.to_string_lossy().nfc().collect::<String>();
-        let path_normalized = PathBuf::from(&path_normalized_str);
-        let path_normalized_lowered = PathBuf::from(&path_normalized_str.to_lowercase());
+    let mut candidates: Vec<&'a PathBuf> = vault_contents
+        .iter()
+        .filter(|path| matches_reference(path, &filename_normalized))
+        .collect();
+
+    if candidates.is_empty() {
+        return LinkResolution::NotFound;
+    }
+
+    candidates.sort_by_key(|path| {
+        (
+            directory_distance(source, path),
+            path.as_os_str().len(),
+            path.to_string_lossy().into_owned(),
+        )
+    });
+
+    let best_distance = directory_distance(source, candidates[0]);
+    let tied: Vec<&'a PathBuf> = candidates
+        .iter()
+        .copied()
+        .take_while(|path| directory_distance(source, path) == best_distance)
+        .collect();
+
+    if tied.len() > 1 {
+        LinkResolution::Ambiguous(tied)
+    } else {
+        LinkResolution::Found(candidates[0])
+    }
+}
+
+fn matches_reference(path: &Path, filename_normalized: &str) -> bool {
+    let path_normalized_str = path.to_string_lossy().nfc().collect::<String>();
+    let path_normalized = PathBuf::from(&path_normalized_str);
+    let path_normalized_lowered = PathBuf::from(&path_normalized_str.to_lowercase());
+
+    path_normalized.ends_with(filename_normalized)
+        || path_normalized.ends_with(filename_normalized.to_owned() + ".md")
+        || path_normalized_lowered.ends_with(filename_normalized.to_lowercase())
+        || path_normalized_lowered.ends_with(filename_normalized.to_lowercase() + ".md")
+}
+
+/// Number of path components separating `candidate` from `source`: the hops from the source
+/// note's directory up to their nearest common ancestor, plus the hops back down to the
+/// candidate's directory.
+fn directory_distance(source: &Path, candidate: &Path) -> usize {
+    let source_dir = source.parent().unwrap_or_else(|| Path::new(""));
+    let candidate_dir = candidate.parent().unwrap_or_else(|| Path::new(""));
+
+    let common = source_dir
+        .components()
+        .zip(candidate_dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
 
-        path_normalized.ends_with(&filename_normalized)
-            || path_normalized.ends_with(filename_normalized.clone() + ".md")
-            || path_normalized_lowered.ends_with(filename_normalized.to_lowercase())
-            || path_normalized_lowered.ends_with(filename_normalized.to_lowercase() + ".md")
-    })
+    (source_dir.components().count() - common) + (candidate_dir.components().count() - common)
 }
 
-pub fn collect_references(content: &str) -> Vec<String> {
+/// Options controlling which references [`collect_references`] extracts from a note.
+#[derive(Debug, Clone, Default)]
+pub struct CollectOptions {
+    /// Frontmatter fields whose whole scalar value should be treated as a bare note name, even
+    /// when it isn't wrapped in `[[ ]]` (e.g. a Dataview-style `up: Parent`).
+    pub link_fields: Vec<String>,
+}
+
+/// What a [`ReferenceFilter`] decides should happen to a discovered reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum FilterAction {
+    /// Pass the reference through unchanged.
+    Keep,
+    /// Drop the reference; it won't be returned by [`Collector::collect`].
+    Skip,
+    /// Pass the reference through, but with its `file` replaced.
+    Replace(String),
+}
+
+/// A postprocessor hook invoked for every reference [`Collector::collect`] discovers, given the
+/// full reference and the note it was found in. Lets library users drop embeds, rewrite
+/// references, or exclude attachments by extension without forking the parser. Any
+/// `Fn(&Reference, &Path) -> FilterAction` closure implements this automatically.
+pub trait ReferenceFilter: Send + Sync {
+    fn apply(&self, reference: &Reference, source: &Path) -> FilterAction;
+}
+
+impl<F> ReferenceFilter for F
+where
+    F: Fn(&Reference, &Path) -> FilterAction + Send + Sync,
+{
+    fn apply(&self, reference: &Reference, source: &Path) -> FilterAction {
+        self(reference, source)
+    }
+}
+
+/// Built-in filter that drops embeds (`![[...]]` / `![alt](...)`), keeping only plain links.
+pub fn no_embeds_filter(reference: &Reference, _source: &Path) -> FilterAction {
+    if reference.embed {
+        FilterAction::Skip
+    } else {
+        FilterAction::Keep
+    }
+}
+
+/// Built-in filter that drops references to anything that isn't a markdown note (i.e. any
+/// reference whose file extension is present and isn't `.md`), excluding attachments such as
+/// images or PDFs.
+pub fn notes_only_filter(reference: &Reference, _source: &Path) -> FilterAction {
+    match PathBuf::from(&reference.file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some(ext) if !ext.eq_ignore_ascii_case("md") => FilterAction::Skip,
+        _ => FilterAction::Keep,
+    }
+}
+
+/// Builder that runs [`collect_references`] through an ordered chain of [`ReferenceFilter`]s.
+#[derive(Default)]
+pub struct Collector {
+    options: CollectOptions,
+    filters: Vec<Box<dyn ReferenceFilter>>,
+}
+
+impl Collector {
+    pub fn new(options: CollectOptions) -> Self {
+        Self {
+            options,
+            filters: Vec::new(),
+        }
+    }
+
+    /// Append a filter to the end of the chain. Filters run in registration order; a reference
+    /// skipped by an earlier filter never reaches later ones.
+    #[must_use]
+    pub fn with_filter(mut self, filter: impl ReferenceFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Collect references from `content` (a note read from `source`) and run them through the
+    /// registered filter chain.
+    pub fn collect(&self, content: &str, source: &Path) -> Vec<Reference> {
+        let mut refs = collect_references(content, &self.options);
+        for filter in &self.filters {
+            refs = refs
+                .into_iter()
+                .filter_map(|reference| match filter.apply(&reference, source) {
+                    FilterAction::Keep => Some(reference),
+                    FilterAction::Skip => None,
+                    FilterAction::Replace(file) => Some(Reference { file, ..reference }),
+                })
+                .collect();
+        }
+        refs
+    }
+}
+
+/// A reference discovered in a note, together with whether it was an embed (`![[...]]` /
+/// `![alt](...)`) as opposed to a plain link, and (for wiki-links) the block/heading anchor and
+/// display label Obsidian allows after the file name, e.g. `[[Note#heading|label]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub file: String,
+    pub embed: bool,
+    pub anchor: Option<String>,
+    pub label: Option<String>,
+}
+
+pub fn collect_references(content: &str, options: &CollectOptions) -> Vec<Reference> {
     let parser_options = Options::ENABLE_TABLES
         | Options::ENABLE_FOOTNOTES
         | Options::ENABLE_STRIKETHROUGH
@@ -60,7 +239,8 @@ pub fn collect_references(content: &str) -> Vec<String> {
 
     let mut parser = MdParser::new_ext(content, parser_options);
     'outer: while let Some(event) = parser.next() {
-        // Collect frontmatter exactly like obsidian-export, but we don't use it.
+        // Collect frontmatter exactly like obsidian-export; the accumulated text is parsed as
+        // YAML below so that wiki-links living in metadata fields are followed too.
         if matches!(event, Event::Start(Tag::MetadataBlock(_))) {
             for event in parser.by_ref() {
                 match event {
@@ -75,6 +255,22 @@ pub fn collect_references(content: &str) -> Vec<String> {
                 }
             }
         }
+        if let Event::Start(tag @ (Tag::Link { .. } | Tag::Image { .. })) = &event {
+            let (dest_url, embed) = match tag {
+                Tag::Link { dest_url, .. } => (dest_url, false),
+                Tag::Image { dest_url, .. } => (dest_url, true),
+                _ => unreachable!(),
+            };
+            if let Some(file) = markdown_link_reference(dest_url) {
+                refs.push(Reference {
+                    file,
+                    embed,
+                    anchor: None,
+                    label: None,
+                });
+            }
+            continue;
+        }
         if ref_parser.state == RefParserState::Resetting {
             buffer.clear();
             ref_parser.reset();
@@ -148,11 +344,16 @@ pub fn collect_references(content: &str) -> Vec<String> {
             },
             RefParserState::ExpectFinalCloseBracket => match event {
                 Event::Text(CowStr::Borrowed("]")) => match ref_parser.ref_type {
-                    Some(RefType::Link) | Some(RefType::Embed) => {
+                    Some(ref_type @ (RefType::Link | RefType::Embed)) => {
                         let raw = ref_parser.ref_text.clone();
                         let note_ref = ObsidianNoteReference::from_str(raw.as_ref());
                         if let Some(file) = note_ref.file {
-                            refs.push(file.to_string());
+                            refs.push(Reference {
+                                file: file.to_string(),
+                                embed: matches!(ref_type, RefType::Embed),
+                                anchor: note_ref.section.map(|section| section.to_string()),
+                                label: note_ref.label.map(|label| label.to_string()),
+                            });
                         }
                         buffer.clear();
                         ref_parser.transition(RefParserState::Resetting);
@@ -170,5 +371,295 @@ pub fn collect_references(content: &str) -> Vec<String> {
         }
     }
 
+    refs.extend(collect_frontmatter_references(
+        &frontmatter,
+        &options.link_fields,
+    ));
+    refs
+}
+
+/// Parse a note's raw frontmatter as YAML and recursively pull out every wiki-link reference
+/// found in string scalars, plus (for fields listed in `link_fields`) the whole scalar value
+/// treated as a bare note name.
+fn collect_frontmatter_references(frontmatter: &str, link_fields: &[String]) -> Vec<Reference> {
+    let mut refs = Vec::new();
+    let Ok(value) = serde_yaml::from_str::<Value>(frontmatter) else {
+        return refs;
+    };
+    walk_frontmatter_value(&value, None, link_fields, &mut refs);
     refs
 }
+
+fn walk_frontmatter_value(
+    value: &Value,
+    key: Option<&str>,
+    link_fields: &[String],
+    refs: &mut Vec<Reference>,
+) {
+    match value {
+        Value::String(text) => {
+            let mut found_wikilink = false;
+            for raw in extract_wikilink_bodies(text) {
+                let note_ref = ObsidianNoteReference::from_str(&raw);
+                if let Some(file) = note_ref.file {
+                    refs.push(Reference {
+                        file: file.to_string(),
+                        embed: false,
+                        anchor: note_ref.section.map(|section| section.to_string()),
+                        label: note_ref.label.map(|label| label.to_string()),
+                    });
+                    found_wikilink = true;
+                }
+            }
+            if !found_wikilink && key.is_some_and(|key| link_fields.iter().any(|f| f == key)) {
+                let file = match ObsidianNoteReference::from_str(text).file {
+                    Some(file) => file.to_string(),
+                    None => text.clone(),
+                };
+                refs.push(Reference {
+                    file,
+                    embed: false,
+                    anchor: None,
+                    label: None,
+                });
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                walk_frontmatter_value(item, key, link_fields, refs);
+            }
+        }
+        Value::Mapping(map) => {
+            for (k, v) in map {
+                walk_frontmatter_value(v, k.as_str(), link_fields, refs);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Turn a CommonMark link/image destination into a reference string, or `None` if it points
+/// outside the vault (an absolute URL or a `mailto:` address). The returned string is
+/// percent-decoded and has any `#fragment` stripped; it's resolved relative to the linking
+/// note's directory first and the vault root second by the caller.
+fn markdown_link_reference(dest_url: &str) -> Option<String> {
+    if is_external_url(dest_url) {
+        return None;
+    }
+    let without_fragment = dest_url.split('#').next().unwrap_or("");
+    if without_fragment.is_empty() {
+        return None;
+    }
+    Some(percent_decode(without_fragment))
+}
+
+fn is_external_url(dest: &str) -> bool {
+    let Some(scheme_end) = dest.find(':') else {
+        return false;
+    };
+    let scheme = &dest[..scheme_end];
+    if scheme.eq_ignore_ascii_case("mailto") {
+        return true;
+    }
+    let is_valid_scheme = !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_valid_scheme && dest[scheme_end..].starts_with("://")
+}
+
+fn percent_decode(input: &str) -> String {
+    // Work on bytes only and never slice `input` itself: a `%` can be immediately followed by a
+    // multi-byte UTF-8 character, and slicing at an arbitrary byte offset would panic with
+    // "byte index is not a char boundary".
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hi = (bytes[i + 1] as char).to_digit(16).unwrap();
+            let lo = (bytes[i + 2] as char).to_digit(16).unwrap();
+            decoded.push((hi * 16 + lo) as u8);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Pull out the raw text between every `[[` / `]]` pair in a plain (non-Markdown) string, such
+/// as a frontmatter scalar.
+fn extract_wikilink_bodies(text: &str) -> Vec<String> {
+    let mut bodies = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        match after.find("]]") {
+            Some(end) => {
+                bodies.push(after[..end].to_string());
+                rest = &after[end + 2..];
+            }
+            None => break,
+        }
+    }
+    bodies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_prefers_the_candidate_closest_to_the_source_note() {
+        let vault = vec![
+            PathBuf::from("vault/a/b/Note.md"),
+            PathBuf::from("vault/a/c/Note.md"),
+        ];
+        let source = PathBuf::from("vault/a/b/Source.md");
+
+        match lookup_filename_in_vault("Note", &vault, &source) {
+            LinkResolution::Found(path) => assert_eq!(path, &vault[0]),
+            other => panic!("expected a unique closest match, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn lookup_reports_equidistant_candidates_as_ambiguous() {
+        let vault = vec![
+            PathBuf::from("vault/x/Note.md"),
+            PathBuf::from("vault/y/Note.md"),
+        ];
+        let source = PathBuf::from("vault/z/Source.md");
+
+        match lookup_filename_in_vault("Note", &vault, &source) {
+            LinkResolution::Ambiguous(candidates) => assert_eq!(candidates.len(), 2),
+            other => panic!("expected an ambiguous match, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn lookup_reports_not_found_when_nothing_matches() {
+        let vault = vec![PathBuf::from("vault/a/Note.md")];
+        let source = PathBuf::from("vault/a/Source.md");
+
+        assert!(matches!(
+            lookup_filename_in_vault("Missing", &vault, &source),
+            LinkResolution::NotFound
+        ));
+    }
+
+    #[test]
+    fn collects_wikilinks_from_frontmatter_scalars() {
+        let frontmatter = "up: \"[[Parent]]\"\nrelated:\n  - \"[[A]]\"\n  - \"[[B|Label]]\"\n";
+
+        let refs = collect_frontmatter_references(frontmatter, &[]);
+        let files: Vec<&str> = refs.iter().map(|r| r.file.as_str()).collect();
+
+        assert_eq!(files, vec!["Parent", "A", "B"]);
+        assert!(refs.iter().all(|r| !r.embed));
+    }
+
+    #[test]
+    fn treats_configured_link_fields_as_bare_note_names() {
+        let frontmatter = "up: Parent\nunrelated: Not A Link\n";
+
+        let refs = collect_frontmatter_references(frontmatter, &["up".to_string()]);
+
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].file, "Parent");
+    }
+
+    #[test]
+    fn markdown_link_reference_skips_external_urls_and_mailto() {
+        assert_eq!(markdown_link_reference("https://example.com/foo"), None);
+        assert_eq!(markdown_link_reference("mailto:a@example.com"), None);
+    }
+
+    #[test]
+    fn markdown_link_reference_strips_fragment_and_decodes() {
+        assert_eq!(
+            markdown_link_reference("assets/Some%20Note.md#heading"),
+            Some("assets/Some Note.md".to_string())
+        );
+    }
+
+    #[test]
+    fn percent_decode_handles_basic_escapes() {
+        assert_eq!(percent_decode("Some%20Note.md"), "Some Note.md");
+    }
+
+    #[test]
+    fn percent_decode_does_not_panic_on_percent_before_a_multibyte_char() {
+        assert_eq!(percent_decode("100%€.md"), "100%€.md");
+    }
+
+    fn reference(file: &str, embed: bool) -> Reference {
+        Reference {
+            file: file.to_string(),
+            embed,
+            anchor: None,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn no_embeds_filter_skips_embeds_and_keeps_plain_links() {
+        let source = Path::new("Source.md");
+        assert_eq!(
+            no_embeds_filter(&reference("Note.md", true), source),
+            FilterAction::Skip
+        );
+        assert_eq!(
+            no_embeds_filter(&reference("Note.md", false), source),
+            FilterAction::Keep
+        );
+    }
+
+    #[test]
+    fn notes_only_filter_skips_attachments_but_keeps_extensionless_names() {
+        let source = Path::new("Source.md");
+        assert_eq!(
+            notes_only_filter(&reference("image.png", false), source),
+            FilterAction::Skip
+        );
+        assert_eq!(
+            notes_only_filter(&reference("Note.md", false), source),
+            FilterAction::Keep
+        );
+        assert_eq!(
+            notes_only_filter(&reference("Note", false), source),
+            FilterAction::Keep
+        );
+    }
+
+    #[test]
+    fn collector_runs_filters_in_registration_order_and_short_circuits_on_skip() {
+        let content = "[[Keep]] ![[Drop]]";
+        let collector = Collector::new(CollectOptions::default())
+            .with_filter(no_embeds_filter)
+            .with_filter(|reference: &Reference, _source: &Path| {
+                FilterAction::Replace(format!("{}.md", reference.file))
+            });
+
+        let refs = collector.collect(content, Path::new("Source.md"));
+
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].file, "Keep.md");
+    }
+
+    #[test]
+    fn collector_with_no_filters_returns_every_collected_reference() {
+        let content = "[[A]] ![[B]]";
+        let collector = Collector::new(CollectOptions::default());
+
+        let refs = collector.collect(content, Path::new("Source.md"));
+
+        assert_eq!(refs.len(), 2);
+    }
+}
@@ -1,12 +1,16 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Context, Result};
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, ValueEnum};
+use rayon::prelude::*;
 use unicode_normalization::UnicodeNormalization;
 
-use wlls::{collect_references, lookup_filename_in_vault, vault_contents, WalkOptions};
+use wlls::{
+    lookup_filename_in_vault, no_embeds_filter, notes_only_filter, vault_contents, CollectOptions,
+    Collector, LinkResolution, WalkOptions,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "wlls", about = "List Obsidian wiki-linked files", version)]
@@ -17,12 +21,51 @@ struct Cli {
     /// Skip unresolved references instead of failing
     #[arg(long = "skip-missing-refs", action = ArgAction::SetTrue)]
     skip_missing_refs: bool,
+    /// Treat ambiguous wiki-link resolution (multiple equally-close candidates) as an error
+    #[arg(long = "strict", action = ArgAction::SetTrue)]
+    strict: bool,
+    /// Frontmatter field whose value should be followed as a bare note name even without
+    /// `[[ ]]` brackets (repeatable)
+    #[arg(long = "link-field")]
+    link_fields: Vec<String>,
+    /// Number of threads to use when scanning notes in parallel (defaults to rayon's own choice)
+    #[arg(long = "jobs")]
+    jobs: Option<usize>,
+    /// Stop following links beyond this many hops from the seed notes (unbounded by default)
+    #[arg(long = "max-depth")]
+    max_depth: Option<usize>,
+    /// Print the discovered source -> target edges instead of a flat sorted file list
+    #[arg(long = "graph", value_enum)]
+    graph: Option<GraphFormat>,
+    /// Drop embeds (`![[...]]` / `![alt](...)`), keeping only plain links
+    #[arg(long = "no-embeds", action = ArgAction::SetTrue)]
+    no_embeds: bool,
+    /// Drop references to anything other than markdown notes (e.g. images, PDFs)
+    #[arg(long = "notes-only", action = ArgAction::SetTrue)]
+    notes_only: bool,
     /// Path to the vault root
     vault_root: PathBuf,
     /// One or more note paths (absolute or vault-relative)
     notes: Vec<PathBuf>,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GraphFormat {
+    /// Graphviz DOT digraph
+    Dot,
+    /// Newline-delimited JSON edge objects
+    Ndjson,
+}
+
+/// A `source -> target` edge in the discovered link graph, tagging whether it came from an
+/// embed (`![[...]]` / `![alt](...)`) or a plain link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edge {
+    from: PathBuf,
+    to: PathBuf,
+    embed: bool,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -30,76 +73,236 @@ fn main() -> Result<()> {
         return Err(anyhow!("At least one note path is required"));
     }
 
+    if let Some(jobs) = cli.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .context("failed to set up the rayon thread pool")?;
+    }
+
     let vault_root = cli
         .vault_root
         .canonicalize()
         .context("vault_root does not exist")?;
     if !vault_root.is_dir() {
-        return Err(anyhow!("vault_root is not a directory: {}", vault_root.display()));
+        return Err(anyhow!(
+            "vault_root is not a directory: {}",
+            vault_root.display()
+        ));
     }
 
     let vault_files = vault_contents(&vault_root, WalkOptions::default())
         .context("failed to enumerate vault contents")?;
+    let mut collector = Collector::new(CollectOptions {
+        link_fields: cli.link_fields.clone(),
+    });
+    if cli.no_embeds {
+        collector = collector.with_filter(no_embeds_filter);
+    }
+    if cli.notes_only {
+        collector = collector.with_filter(notes_only_filter);
+    }
 
-    let mut queue = VecDeque::new();
     let mut outputs = HashSet::new();
     let mut visited = HashSet::new();
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut frontier = Vec::new();
     for note in &cli.notes {
         let resolved = resolve_input_note(note, &vault_root, &vault_files)
             .with_context(|| format!("invalid input note: {}", note.display()))?;
-        outputs.insert(resolved.clone());
-        queue.push_back(resolved);
+        if visited.insert(resolved.clone()) {
+            outputs.insert(resolved.clone());
+            frontier.push((resolved, 0));
+        }
+    }
+
+    while !frontier.is_empty() {
+        let processed: Vec<NoteOutcome> = frontier
+            .par_iter()
+            .map(|(note, depth)| {
+                process_note(note, *depth, &vault_files, &vault_root, &collector, &cli)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        frontier = advance_frontier(
+            processed,
+            cli.recursive,
+            cli.max_depth,
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
     }
 
-    while let Some(note) = queue.pop_front() {
-        if !visited.insert(note.clone()) {
-            continue;
+    match cli.graph {
+        Some(format) => print!("{}", render_graph(&edges, format)),
+        None => {
+            let mut sorted: Vec<_> = outputs.into_iter().collect();
+            sorted.sort();
+            for path in sorted {
+                println!("{}", path.display());
+            }
         }
-        let content = fs::read_to_string(&note)
-            .with_context(|| format!("failed to read note {}", note.display()))?;
-        let refs = collect_references(&content);
+    }
+
+    Ok(())
+}
+
+/// Render the discovered edges as a DOT digraph or as newline-delimited JSON, sorted by
+/// `(from, to, embed)` so the output is stable across runs.
+fn render_graph(edges: &[Edge], format: GraphFormat) -> String {
+    let mut sorted: Vec<&Edge> = edges.iter().collect();
+    sorted.sort_by(|a, b| (&a.from, &a.to, a.embed).cmp(&(&b.from, &b.to, b.embed)));
 
-        for raw_ref in refs {
-            let Some(target) = resolve_reference(&raw_ref, &vault_files) else {
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph wlls {\n");
+            for edge in sorted {
+                out.push_str(&format!(
+                    "  {:?} -> {:?} [embed={}];\n",
+                    edge.from.display().to_string(),
+                    edge.to.display().to_string(),
+                    edge.embed
+                ));
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::Ndjson => {
+            for edge in sorted {
+                out.push_str(&format!(
+                    "{{\"from\":{},\"to\":{},\"embed\":{}}}\n",
+                    json_string(&edge.from.display().to_string()),
+                    json_string(&edge.to.display().to_string()),
+                    edge.embed
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The edges discovered from a single note, ready to be unioned into the shared output/frontier
+/// state by the (single-threaded) caller, along with the note's own distance from the seeds.
+#[derive(Debug, Clone)]
+struct NoteOutcome {
+    depth: usize,
+    edges: Vec<Edge>,
+}
+
+/// Merge a round of [`NoteOutcome`]s into the shared `outputs`/`visited`/`edges` state and return
+/// the next round's frontier. Every discovered target is always recorded in `outputs`; it's only
+/// queued for the next round when `--recursive` is set, it's still inside `max_depth`, it's a
+/// markdown note (not an attachment), and it hasn't been visited before.
+fn advance_frontier(
+    processed: Vec<NoteOutcome>,
+    recursive: bool,
+    max_depth: Option<usize>,
+    outputs: &mut HashSet<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+    edges: &mut Vec<Edge>,
+) -> Vec<(PathBuf, usize)> {
+    let mut next_frontier = Vec::new();
+    for outcome in processed {
+        let can_expand = recursive && max_depth.is_none_or(|max_depth| outcome.depth < max_depth);
+        for edge in outcome.edges {
+            outputs.insert(edge.to.clone());
+            if can_expand && is_markdown(&edge.to) && visited.insert(edge.to.clone()) {
+                next_frontier.push((edge.to.clone(), outcome.depth + 1));
+            }
+            edges.push(edge);
+        }
+    }
+    next_frontier
+}
+
+fn process_note(
+    note: &Path,
+    depth: usize,
+    vault_files: &[PathBuf],
+    vault_root: &Path,
+    collector: &Collector,
+    cli: &Cli,
+) -> Result<NoteOutcome> {
+    let content = fs::read_to_string(note)
+        .with_context(|| format!("failed to read note {}", note.display()))?;
+    let refs = collector.collect(&content, note);
+
+    let mut edges = Vec::with_capacity(refs.len());
+    for reference in refs {
+        let target = match resolve_reference(&reference.file, vault_files, note, vault_root) {
+            LinkResolution::Found(path) => path.clone(),
+            LinkResolution::Ambiguous(candidates) => {
+                let listing = candidates
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if cli.strict {
+                    return Err(anyhow!(
+                        "ambiguous reference '{}' from {} resolves to multiple equally close notes: {}",
+                        reference.file,
+                        note.display(),
+                        listing
+                    ));
+                }
+                eprintln!(
+                    "warning: ambiguous reference '{}' from {} resolves to multiple equally close notes: {}; using {}",
+                    reference.file,
+                    note.display(),
+                    listing,
+                    candidates[0].display()
+                );
+                candidates[0].clone()
+            }
+            LinkResolution::NotFound => {
                 if cli.skip_missing_refs {
                     eprintln!(
                         "warning: skipping unresolved reference '{}' from {}",
-                        raw_ref,
+                        reference.file,
                         note.display()
                     );
                     continue;
                 }
                 return Err(anyhow!(
                     "could not resolve reference '{}' from {}",
-                    raw_ref,
+                    reference.file,
                     note.display()
                 ));
-            };
-            let target = target
-                .canonicalize()
-                .with_context(|| format!("failed to canonicalize {}", target.display()))?;
-            outputs.insert(target.clone());
-
-            if cli.recursive && is_markdown(&target) {
-                queue.push_back(target);
             }
-        }
+        };
+        let target = target
+            .canonicalize()
+            .with_context(|| format!("failed to canonicalize {}", target.display()))?;
+        edges.push(Edge {
+            from: note.to_path_buf(),
+            to: target,
+            embed: reference.embed,
+        });
     }
 
-    let mut sorted: Vec<_> = outputs.into_iter().collect();
-    sorted.sort();
-    for path in sorted {
-        println!("{}", path.display());
-    }
-
-    Ok(())
+    Ok(NoteOutcome { depth, edges })
 }
 
-fn resolve_input_note(
-    note: &Path,
-    vault_root: &Path,
-    vault_files: &[PathBuf],
-) -> Result<PathBuf> {
+fn resolve_input_note(note: &Path, vault_root: &Path, vault_files: &[PathBuf]) -> Result<PathBuf> {
     let path = if note.is_absolute() {
         note.to_path_buf()
     } else {
@@ -128,8 +331,58 @@ fn same_file(a: &Path, b: &Path) -> bool {
     normalize_path(a) == normalize_path(b)
 }
 
-fn resolve_reference(reference: &str, vault_contents: &[PathBuf]) -> Option<PathBuf> {
-    lookup_filename_in_vault(reference, vault_contents).cloned()
+/// Resolve a collected reference to a vault file. Path-shaped references (from Markdown links
+/// and image embeds) are first tried relative to the linking note's directory, then relative to
+/// the vault root, exactly as Obsidian renders them; anything left over falls back to the
+/// proximity-ranked wiki-link matcher for bare note names.
+fn resolve_reference<'a>(
+    reference: &str,
+    vault_contents: &'a [PathBuf],
+    source: &Path,
+    vault_root: &Path,
+) -> LinkResolution<'a> {
+    // A leading `/` means "relative to the vault root", same as Obsidian renders it. Feeding
+    // this straight into `join_relative` would push an absolute `RootDir` component, and
+    // `PathBuf::push` replaces the whole path with it instead of joining - silently discarding
+    // `vault_root` and sending us looking for a literal filesystem path.
+    if let Some(root_relative) = reference.strip_prefix('/') {
+        if let Some(path) = find_relative(vault_root, root_relative, vault_contents) {
+            return LinkResolution::Found(path);
+        }
+        return lookup_filename_in_vault(root_relative, vault_contents, source);
+    }
+    if let Some(note_dir) = source.parent() {
+        if let Some(path) = find_relative(note_dir, reference, vault_contents) {
+            return LinkResolution::Found(path);
+        }
+    }
+    if let Some(path) = find_relative(vault_root, reference, vault_contents) {
+        return LinkResolution::Found(path);
+    }
+    lookup_filename_in_vault(reference, vault_contents, source)
+}
+
+fn find_relative<'a>(
+    base: &Path,
+    reference: &str,
+    vault_contents: &'a [PathBuf],
+) -> Option<&'a PathBuf> {
+    let joined = join_relative(base, Path::new(reference));
+    vault_contents.iter().find(|p| same_file(p, &joined))
+}
+
+fn join_relative(base: &Path, reference: &Path) -> PathBuf {
+    let mut result = base.to_path_buf();
+    for component in reference.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                result.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
 }
 
 fn is_markdown(path: &Path) -> bool {
@@ -139,3 +392,159 @@ fn is_markdown(path: &Path) -> bool {
 fn normalize_path(path: &Path) -> String {
     path.to_string_lossy().nfc().collect::<String>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: &str, to: &str, embed: bool) -> Edge {
+        Edge {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+            embed,
+        }
+    }
+
+    #[test]
+    fn advance_frontier_does_not_expand_without_recursive() {
+        let mut outputs = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut edges = Vec::new();
+        let processed = vec![NoteOutcome {
+            depth: 0,
+            edges: vec![edge("A.md", "B.md", false)],
+        }];
+
+        let next = advance_frontier(
+            processed,
+            false,
+            None,
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
+
+        assert!(next.is_empty());
+        assert_eq!(edges.len(), 1);
+        assert!(outputs.contains(&PathBuf::from("B.md")));
+    }
+
+    #[test]
+    fn advance_frontier_expands_markdown_but_not_attachments() {
+        let mut outputs = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut edges = Vec::new();
+        let processed = vec![NoteOutcome {
+            depth: 0,
+            edges: vec![edge("A.md", "B.md", false), edge("A.md", "image.png", true)],
+        }];
+
+        let next = advance_frontier(
+            processed,
+            true,
+            None,
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
+
+        assert_eq!(next, vec![(PathBuf::from("B.md"), 1)]);
+    }
+
+    #[test]
+    fn advance_frontier_does_not_revisit_an_already_visited_note() {
+        let mut outputs = HashSet::new();
+        let mut visited = HashSet::new();
+        visited.insert(PathBuf::from("B.md"));
+        let mut edges = Vec::new();
+        let processed = vec![NoteOutcome {
+            depth: 0,
+            edges: vec![edge("A.md", "B.md", false)],
+        }];
+
+        let next = advance_frontier(
+            processed,
+            true,
+            None,
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
+
+        assert!(next.is_empty());
+    }
+
+    #[test]
+    fn advance_frontier_stops_expanding_once_max_depth_is_reached() {
+        let mut outputs = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut edges = Vec::new();
+        let processed = vec![NoteOutcome {
+            depth: 1,
+            edges: vec![edge("A.md", "B.md", false)],
+        }];
+
+        let next = advance_frontier(
+            processed,
+            true,
+            Some(1),
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
+
+        assert!(next.is_empty());
+        assert!(outputs.contains(&PathBuf::from("B.md")));
+    }
+
+    #[test]
+    fn advance_frontier_expands_when_still_under_max_depth() {
+        let mut outputs = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut edges = Vec::new();
+        let processed = vec![NoteOutcome {
+            depth: 0,
+            edges: vec![edge("A.md", "B.md", false)],
+        }];
+
+        let next = advance_frontier(
+            processed,
+            true,
+            Some(1),
+            &mut outputs,
+            &mut visited,
+            &mut edges,
+        );
+
+        assert_eq!(next, vec![(PathBuf::from("B.md"), 1)]);
+    }
+
+    #[test]
+    fn render_graph_as_dot_sorts_edges_and_quotes_paths() {
+        let edges = vec![edge("B.md", "A.md", false), edge("A.md", "A.md", true)];
+
+        let rendered = render_graph(&edges, GraphFormat::Dot);
+
+        assert_eq!(
+            rendered,
+            "digraph wlls {\n  \"A.md\" -> \"A.md\" [embed=true];\n  \"B.md\" -> \"A.md\" [embed=false];\n}\n"
+        );
+    }
+
+    #[test]
+    fn render_graph_as_ndjson_emits_one_object_per_line() {
+        let edges = vec![edge("A.md", "B.md", false)];
+
+        let rendered = render_graph(&edges, GraphFormat::Ndjson);
+
+        assert_eq!(
+            rendered,
+            "{\"from\":\"A.md\",\"to\":\"B.md\",\"embed\":false}\n"
+        );
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_backslashes_and_control_characters() {
+        assert_eq!(json_string("a\"b\\c\nd"), "\"a\\\"b\\\\c\\nd\"");
+    }
+}